@@ -0,0 +1,69 @@
+use std::cell::Cell;
+use std::sync::atomic::spin_loop_hint;
+use std::thread;
+
+const SPIN_LIMIT: u32 = 6;
+
+/// A helper for exponential backoff in spin loops.
+///
+/// Retrying a failed CAS or waiting for a concurrently written value in a
+/// tight loop wastes CPU cycles and creates needless contention on the
+/// cache line being polled. `Backoff` escalates from a handful of
+/// `spin_loop_hint` iterations to yielding the thread entirely once a spin
+/// threshold is crossed, modeled on crossbeam's `Backoff`.
+pub(crate) struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    /// Creates a new `Backoff` at its initial (most aggressive) step.
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self { step: Cell::new(0) }
+    }
+
+    /// Resets the backoff to its initial step.
+    #[inline]
+    pub(crate) fn reset(&self) {
+        self.step.set(0);
+    }
+
+    /// Backs off in a lock-free CAS retry loop.
+    ///
+    /// Only ever busy-spins; never yields the thread, since a CAS retry
+    /// loop must not block.
+    #[inline]
+    pub(crate) fn spin(&self) {
+        for _ in 0..1u32 << self.step.get().min(SPIN_LIMIT) {
+            spin_loop_hint();
+        }
+
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Backs off while waiting for a condition that another thread must
+    /// bring about (e.g. a concurrently written value becoming visible).
+    ///
+    /// Escalates to yielding the thread once [`is_completed`](Backoff::is_completed).
+    #[inline]
+    pub(crate) fn snooze(&self) {
+        if self.is_completed() {
+            thread::yield_now();
+        } else {
+            for _ in 0..1u32 << self.step.get() {
+                spin_loop_hint();
+            }
+
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Returns `true` once the backoff has escalated past pure spinning and
+    /// should yield the thread instead.
+    #[inline]
+    pub(crate) fn is_completed(&self) -> bool {
+        self.step.get() > SPIN_LIMIT
+    }
+}
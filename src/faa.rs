@@ -3,7 +3,7 @@ use std::cmp;
 use std::mem::{self, MaybeUninit};
 use std::ptr;
 use std::sync::atomic::{
-    spin_loop_hint, AtomicUsize,
+    AtomicUsize,
     Ordering::{Acquire, Relaxed, Release, SeqCst},
 };
 
@@ -12,10 +12,14 @@ use reclaim::prelude::*;
 use reclaim::typenum::U0;
 use reclaim::GlobalReclaim;
 
+use crate::backoff::Backoff;
+
 type Atomic<T, R> = reclaim::Atomic<T, R, U0>;
 type Owned<T, R> = reclaim::Owned<T, R, U0>;
 type Shared<'g, T, R> = reclaim::Shared<'g, T, R, U0>;
 
+const NODE_SIZE: usize = 1024;
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Queue
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -37,8 +41,6 @@ impl<T, R: GlobalReclaim> Default for Queue<T, R> {
 /********** impl inherent *************************************************************************/
 
 impl<T, R: GlobalReclaim> Queue<T, R> {
-    const READ_RETRIES: usize = 128;
-
     #[inline]
     pub fn new() -> Self {
         let head: Owned<Node<T, R>, R> = Owned::new(Node::new());
@@ -52,14 +54,20 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
         }
     }
 
+    /// Pushes `elem` to the tail of the queue, growing a new segment if the
+    /// current one is full.
+    ///
+    /// This always succeeds.
     #[inline]
     pub fn push(&self, mut elem: T) {
         let mut guard = R::guard();
+        let backoff = Backoff::new();
         loop {
             let tail = self.tail.load(Relaxed, &mut guard).unwrap();
             let idx: usize = tail.push_idx.fetch_add(1, SeqCst); // Acquire?
             if idx >= NODE_SIZE {
                 if self.tail.load_raw(Relaxed) != tail.as_marked_ptr() {
+                    backoff.spin();
                     continue;
                 }
 
@@ -67,6 +75,7 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
                     Ok(_) => return,
                     Err(e) => {
                         elem = e;
+                        backoff.spin();
                         continue;
                     }
                 };
@@ -75,6 +84,7 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
                 unsafe { slot.write_tentative(&elem) };
                 let prev = slot.state.fetch_or(WRITER, Release);
                 if prev == READER {
+                    backoff.spin();
                     continue;
                 }
 
@@ -83,9 +93,12 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
         }
     }
 
+    /// Attempts to pop an element from the head of the queue, returning
+    /// [`None`] if the queue is empty.
     #[inline]
     pub fn pop(&self) -> Option<T> {
         let mut guard = R::guard();
+        let backoff = Backoff::new();
         loop {
             let head = self.head.load(SeqCst, &mut guard).unwrap();
 
@@ -99,17 +112,11 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
             let idx: usize = head.pop_idx.fetch_add(1, SeqCst);
             if idx < NODE_SIZE {
                 let slot = &head.elements[idx];
-
-                for _ in 0..Self::READ_RETRIES {
-                    if slot.state.load(Relaxed) == WRITER {
-                        break;
-                    }
-
-                    spin_loop_hint(); // FIXME: use back-off
-                }
+                wait_for_writer(slot);
 
                 let prev = slot.state.fetch_or(READER, Acquire);
                 if prev == UNINIT {
+                    backoff.spin();
                     continue;
                 }
 
@@ -125,6 +132,59 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
                     }
                     None => return None,
                 };
+                backoff.spin();
+            }
+        }
+    }
+
+    /// Blocks the calling thread until an element is available, then pops and
+    /// returns it.
+    ///
+    /// Unlike [`pop`](Queue::pop), this never returns [`None`]. Rather than
+    /// spinning on [`pop`]'s full head/tail traversal on every attempt, the
+    /// calling thread reserves a single upcoming slot - the same index
+    /// [`pop`] would itself claim next - and then waits on just that slot's
+    /// publish flag via [`wait_for_writer`], a cheap, local read, until a
+    /// matching `push` fills it in.
+    ///
+    /// If that particular `push` is simply too slow, [`wait_for_writer`]'s
+    /// own backoff budget runs out and the reservation is abandoned exactly
+    /// as an impatient [`pop`] would abandon it: marked `READER` so that the
+    /// eventual late `push` notices and skips over it rather than publishing
+    /// into a slot nobody is waiting on any more, and this thread reserves
+    /// the next slot instead.
+    #[inline]
+    pub fn pop_wait(&self) -> T {
+        loop {
+            let mut guard = R::guard();
+            let head = self.head.load(SeqCst, &mut guard).unwrap();
+
+            let pop_idx = head.pop_idx.load(SeqCst);
+            let push_idx = head.push_idx.load(SeqCst);
+
+            if pop_idx >= push_idx && head.next.load_unprotected(SeqCst).is_none() {
+                // nothing claimable anywhere in the queue yet; wait for either a
+                // `push` into this node or a new node to appear before retrying
+                Backoff::new().snooze();
+                continue;
+            }
+
+            let idx: usize = head.pop_idx.fetch_add(1, SeqCst);
+            if idx < NODE_SIZE {
+                let slot = &head.elements[idx];
+                wait_for_writer(slot);
+
+                let prev = slot.state.fetch_or(READER, Acquire);
+                if prev == UNINIT {
+                    // gave up waiting on this slot - reserve a fresh one instead
+                    continue;
+                }
+
+                return unsafe { slot.read() };
+            } else {
+                if let Some(next) = head.next.load_unprotected(SeqCst) {
+                    let _ = self.head.compare_exchange(head, next, SeqCst, Relaxed);
+                }
             }
         }
     }
@@ -133,7 +193,8 @@ impl<T, R: GlobalReclaim> Queue<T, R> {
     fn push_new_node(&self, tail: Shared<Node<T, R>, R>, elem: T) -> Result<(), T> {
         match tail.next.load_unprotected(SeqCst) {
             None => {
-                let node: Owned<Node<T, R>, R> = unsafe { Owned::new(Node::with_tentative(&elem)) };
+                let node: Owned<Node<T, R>, R> =
+                    unsafe { Owned::new(Node::with_tentative(&elem)) };
                 match tail
                     .next
                     .compare_exchange(Shared::none(), node, SeqCst, Relaxed)
@@ -173,8 +234,6 @@ impl<T, R: GlobalReclaim> Drop for Queue<T, R> {
 // Node
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-const NODE_SIZE: usize = 1024;
-
 struct Node<T, R> {
     push_idx: CacheAligned<AtomicUsize>,
     pop_idx: CacheAligned<AtomicUsize>,
@@ -281,3 +340,18 @@ impl<T> Slot<T> {
             .copy_from_nonoverlapping(elem, 1);
     }
 }
+
+/// Spins with exponential backoff until `slot` has been published by a
+/// writer, or the backoff's spin budget is exhausted - whichever comes
+/// first.
+///
+/// This does not block indefinitely: if the matching `push` is simply slow,
+/// the caller proceeds to claim the slot anyway and, finding it still
+/// `UNINIT`, retries on a different one.
+#[inline]
+fn wait_for_writer<T>(slot: &Slot<T>) {
+    let backoff = Backoff::new();
+    while slot.state.load(Relaxed) != WRITER && !backoff.is_completed() {
+        backoff.spin();
+    }
+}
@@ -1,24 +1,73 @@
-use std::cell::UnsafeCell;
-use std::cmp;
-use std::mem::{self, MaybeUninit};
-use std::ptr;
-use std::sync::atomic::{
-    AtomicPtr, AtomicUsize,
-    Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst},
-};
+use core::cell::Cell;
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use reclaim::prelude::*;
+use reclaim::GlobalReclaim;
+
+use crate::facade::{AtomicBool, AtomicUsize, UnsafeCell};
+
+type Atomic<T, R> = reclaim::Atomic<T, R, reclaim::typenum::U0>;
+type Owned<T, R> = reclaim::Owned<T, R, reclaim::typenum::U0>;
+type Shared<'g, T, R> = reclaim::Shared<'g, T, R, reclaim::typenum::U0>;
+
+/// The default segment size used by [`Queue::new`], [`Queue::bounded`] and
+/// [`Queue::with_recycle`], i.e. any constructor that does not pin down `N`
+/// explicitly.
+const DEFAULT_NODE_SIZE: usize = 1024;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Recycle
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A pluggable policy for resetting and reusing `T` values across a
+/// [`Queue`]'s pushes and pops, avoiding a construct/destruct cycle for
+/// every element. See [`Queue::with_recycle`].
+pub trait Recycle<T> {
+    /// Creates a new element to seed the queue with.
+    fn new_element() -> T;
+
+    /// Resets `elem` in place so it is fit to be handed out again.
+    fn recycle(&self, elem: &mut T);
+}
+
+/// The type-erased form of a [`Recycle`] implementation, stored in a
+/// [`Queue`] constructed via [`Queue::with_recycle`].
+///
+/// `new_element` is kept as a plain function pointer, since
+/// [`Recycle::new_element`] takes no `self` and so cannot be dispatched
+/// through a trait object; `reset` closes over the recycler instance.
+struct RecyclePolicy<T> {
+    new_element: fn() -> T,
+    reset: Box<dyn Fn(&mut T) + Send + Sync>,
+}
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Queue
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-pub struct Queue<T> {
-    head: AtomicPtr<Node<T>>,
-    tail: AtomicPtr<Node<T>>,
+/// A segment size of `N` slots per node, i.e. per allocation.
+pub struct Queue<T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    head: Atomic<Node<T, R, N>, R>,
+    tail: Atomic<Node<T, R, N>, R>,
+    recycle: Option<RecyclePolicy<T>>,
+    len: AtomicUsize,
+    capacity: usize,
+    closed: AtomicBool,
 }
 
 /********** impl Default **************************************************************************/
 
-impl<T> Default for Queue<T> {
+impl<T, R: GlobalReclaim, const N: usize> Default for Queue<T, R, N> {
     #[inline]
     fn default() -> Self {
         Self::new()
@@ -27,187 +76,779 @@ impl<T> Default for Queue<T> {
 
 /********** impl inherent *************************************************************************/
 
-impl<T> Queue<T> {
-    const RETRY_READ: usize = 128;
-
+impl<T, R: GlobalReclaim, const N: usize> Queue<T, R, N> {
     #[inline]
     pub fn new() -> Self {
-        let head = Box::leak(box Node::new());
+        let head: Owned<Node<T, R, N>, R> = Owned::new(Node::new());
+        let ptr = Owned::into_marked_ptr(head);
 
-        Self {
-            head: AtomicPtr::new(head),
-            tail: AtomicPtr::new(head),
+        // this is safe as long as methods like `Atomic::take` that assume the
+        // Atomic to be the owner of its value are only called on EITHER the
+        // head or the tail
+        unsafe {
+            Self {
+                head: Atomic::from_raw(ptr),
+                tail: Atomic::from_raw(ptr),
+                recycle: None,
+                len: AtomicUsize::new(0),
+                capacity: usize::max_value(),
+                closed: AtomicBool::new(false),
+            }
         }
     }
 
+    /// Creates a new, empty [`Queue`] that uses `recycler` to reset popped
+    /// elements in place (e.g. clearing a `Vec`/`String` while keeping its
+    /// capacity) instead of dropping and reallocating them on every cycle.
+    ///
+    /// Note: this only covers the element itself, via
+    /// [`pop_recycle`](Queue::pop_recycle) and [`push_new`](Queue::push_new)
+    /// - slot storage is not reused: each segment is reclaimed as a whole once
+    /// fully drained, same as in the non-pooling case, and a
+    /// [`bounded`](Queue::bounded) queue behaves the same way.
     #[inline]
-    pub fn push(&self, mut elem: T) {
+    pub fn with_recycle<C>(recycler: C) -> Self
+    where
+        C: Recycle<T> + Send + Sync + 'static,
+    {
+        let mut queue = Self::new();
+        queue.recycle = Some(RecyclePolicy {
+            new_element: C::new_element,
+            reset: Box::new(move |elem: &mut T| recycler.recycle(elem)),
+        });
+        queue
+    }
+
+    /// Pushes a freshly minted element (via the queue's [`Recycle`] policy)
+    /// onto the tail of the queue; see [`push`](Queue::push) for when this
+    /// can fail.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue was not constructed via
+    /// [`with_recycle`](Queue::with_recycle).
+    #[inline]
+    pub fn push_new(&self) -> Result<(), T> {
+        let new_element = self
+            .recycle
+            .as_ref()
+            .expect("`Queue::push_new` requires a `Recycle` policy, see `Queue::with_recycle`")
+            .new_element;
+        self.push(new_element())
+    }
+
+    /// Pops an element and resets it in place via the queue's [`Recycle`]
+    /// policy before handing it to the caller, so callers never observe
+    /// leftover state from a previous occupant of the slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue was not constructed via
+    /// [`with_recycle`](Queue::with_recycle).
+    #[inline]
+    pub fn pop_recycle(&self) -> Option<T> {
+        let policy = self
+            .recycle
+            .as_ref()
+            .expect("`Queue::pop_recycle` requires a `Recycle` policy, see `Queue::with_recycle`");
+        self.pop().map(|mut elem| {
+            (policy.reset)(&mut elem);
+            elem
+        })
+    }
+
+    /// Closes the queue, so that subsequent [`push`](Queue::push) calls are
+    /// rejected; buffered elements remain poppable until drained.
+    ///
+    /// A `push` already past this check when `close` is called still
+    /// completes normally - `close` only ever turns away calls that have not
+    /// yet committed a slot, it never reaches into an in-flight one.
+    ///
+    /// Returns `true` if this call is the one that closed the queue, `false`
+    /// if it was already closed.
+    #[inline]
+    pub fn close(&self) -> bool {
+        !self.closed.swap(true, AcqRel)
+    }
+
+    /// Returns `true` if the queue has been [`close`](Queue::close)d.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Acquire)
+    }
+
+    /// Creates a new, empty [`Queue`] that rejects [`push`](Queue::push)
+    /// calls once it holds `capacity` elements, same as [`close`](Queue::close)
+    /// would, until enough [`pop`](Queue::pop)s bring it back under the bound.
+    #[inline]
+    pub fn bounded(capacity: usize) -> Self {
+        let mut queue = Self::new();
+        queue.capacity = capacity;
+        queue
+    }
+
+    /// Returns a momentary snapshot of how many elements the queue is
+    /// holding; concurrent `push`/`pop` calls may render it stale the instant
+    /// it returns.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Acquire)
+    }
+
+    /// Returns `true` if [`len`](Queue::len) was `0` at the time of the call.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue was at its [`capacity`](Queue::capacity)
+    /// bound at the time of the call, i.e. a concurrent `push` may have been
+    /// rejected for the same reason a [`close`](Queue::close)d queue would
+    /// reject one.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    /// Returns the `push` bound set by [`bounded`](Queue::bounded), or
+    /// [`usize::max_value`] for a queue created via [`new`](Queue::new).
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Pushes `elem` onto the tail of the queue, unless the queue is
+    /// [`bounded`](Queue::bounded) and already full or has been
+    /// [`close`](Queue::close)d, in which case `elem` is handed back to the
+    /// caller.
+    #[inline]
+    pub fn push(&self, mut elem: T) -> Result<(), T> {
+        if self.closed.load(Acquire) {
+            return Err(elem);
+        }
+
+        let mut len = self.len.load(Acquire);
         loop {
-            let tail_ptr = self.tail.load(Acquire);
-            let tail = unsafe { &*tail_ptr };
+            if len >= self.capacity {
+                return Err(elem);
+            }
+
+            match self
+                .len
+                .compare_exchange_weak(len, len + 1, AcqRel, Acquire)
+            {
+                Ok(_) => break,
+                Err(current) => len = current,
+            }
+        }
+
+        let mut guard = R::guard();
+        let backoff = Backoff::new();
+        loop {
+            let tail = self.tail.load(Relaxed, &mut guard).unwrap();
             let idx: usize = tail.push_idx.fetch_add(1, AcqRel);
 
-            if idx >= NODE_SIZE {
-                if self.tail.load(Relaxed) != tail_ptr {
+            if idx >= N {
+                if self.tail.load_raw(Relaxed) != tail.as_marked_ptr() {
+                    backoff.spin();
                     continue;
                 }
 
-                match unsafe { self.insert_new_node(tail_ptr, elem) } {
-                    Ok(_) => return,
+                match self.insert_new_node(tail, elem) {
+                    Ok(_) => return Ok(()),
                     Err(e) => {
                         elem = e;
+                        backoff.spin();
                         continue;
                     }
                 };
             } else {
                 let slot = &tail.elements[idx];
-                // FIXME:
-                //  - write tentative
-                //  - CAS state(UNINIT, INIT)
-                //  - success: forget elem, return
-                //  - failure: state == ABANDONED, retry
                 unsafe { slot.write(elem) };
-                // FIXME: slot.state.store(INIT; release); // or fetch_or? deps on pop
-                //   slot.init.store(true, Release);
-                return;
+
+                match slot
+                    .state
+                    .compare_exchange(UNINIT, INIT, AcqRel, Acquire)
+                {
+                    Ok(_) => return Ok(()),
+                    Err(_) => {
+                        // a `pop` gave up waiting on this slot (ABANDONED) before this write
+                        // finished; reclaim the value and retry on a fresh slot instead of
+                        // leaving it buried in one no `pop` will ever look at again
+                        elem = unsafe { slot.read() };
+                        backoff.spin();
+                        continue;
+                    }
+                }
             }
         }
     }
 
+    /// Alias for [`push`](Queue::push), spelled out for callers reaching for
+    /// a bounded-queue backpressure method by name; `push` is already
+    /// fallible for exactly this reason.
+    #[inline]
+    pub fn try_push(&self, elem: T) -> Result<(), T> {
+        self.push(elem)
+    }
+
     #[inline]
     pub fn pop(&self) -> Option<T> {
+        let mut guard = R::guard();
+        let backoff = Backoff::new();
         loop {
-            let head_ptr = self.head.load(Acquire);
-            let head = unsafe { &*head_ptr };
+            let head = self.head.load(Acquire, &mut guard).unwrap();
 
-            let pop_idx = head.pop_idx.load(AcqRel);
-            let push_idx = head.push_idx.load(AcqRel);
+            let pop_idx = head.pop_idx.load(Acquire);
+            let push_idx = head.push_idx.load(Acquire);
 
-            if push_idx >= pop_idx && head.next.load(SeqCst).is_null() {
+            if pop_idx >= push_idx && head.next.load_unprotected(Acquire).is_none() {
                 return None;
             }
 
-            let idx: usize = head.pop_idx.fetch_add(1, SeqCst);
+            let idx: usize = head.pop_idx.fetch_add(1, AcqRel);
 
-            if idx < NODE_SIZE - 1 {
+            if idx < N {
                 let slot = &head.elements[idx];
 
-                // loop x times while state UNINIT
-                // if init -> fetch_or(CONSUMED, AcqRel) + read value;
-                // else CAS(UNINIT, ABANDONED)
-                //   success: continue; // slot is abandoned, won't be written to ever (init destruction)
-                //   failure: fetch_or(CONSUMED, AcqRel) + read value
+                // the matching `push` may still be in the middle of its tentative write, so
+                // give it a chance to catch up before conceding the slot as unreachable
+                let mut state = wait_for_init(slot);
 
-                for _ in 0..Self::RETRY_READ {
-                    const RDY: usize = 88;
-                    if slot.state.load(Acquire) == RDY {
-                        break;
+                if state == UNINIT {
+                    // the matching `push` is either abandoned or simply slower than
+                    // `wait_for_init`'s backoff budget; give up on this slot so the `push`
+                    // notices on its own `compare_exchange` and relocates, rather than
+                    // blocking here
+                    match slot.state.compare_exchange(UNINIT, ABANDONED, AcqRel, Acquire) {
+                        Ok(_) => {
+                            self.note_resolved(head);
+                            continue;
+                        }
+                        // lost the race to a `push` that finished in between - its value is
+                        // there to read after all
+                        Err(now) => state = now,
                     }
-
-                    // back-off
                 }
 
-            //
+                debug_assert_eq!(state, INIT);
+                let elem = unsafe { slot.read() };
+                slot.state.store(CONSUMED, Release);
+                self.len.fetch_sub(1, Relaxed);
+                self.note_resolved(head);
 
-            // what if node uninit? -> spin a few times, else abandon the slot
-            // read value and destroy slot, may take over node destruction (cold)
-            } else if idx == NODE_SIZE - 1 {
-                // read value, initiate node destruction, try to unlink node
+                return Some(elem);
             } else {
-                // try help to unlink
+                // this segment is exhausted - help unlink it and retry against its successor
+                self.try_retire(head);
+                backoff.spin();
             }
+        }
+    }
 
-            if idx >= NODE_SIZE {
-                match head.next.load(Acquire) {
-                    ptr if ptr.is_null() => return None,
-                    next => {
-                        if self.head.compare_and_swap(head_ptr, next, Release) == head_ptr {
-                            unimplemented!()
+    /// Reserves a free slot at the tail of the queue and returns a guard
+    /// granting direct write access to its (uninitialized) storage.
+    ///
+    /// Mirrors [`push`](Queue::push), but for large `T` avoids the extra move
+    /// it incurs: the caller constructs the value directly in place via
+    /// [`SlotRef`]'s [`DerefMut`] impl. The element is published to consumers
+    /// once the returned guard is dropped.
+    ///
+    /// Returns [`Full`] instead of reserving a slot if the queue is already
+    /// holding [`capacity`](Queue::capacity) elements or has been
+    /// [`close`](Queue::close)d.
+    #[inline]
+    pub fn push_ref(&self) -> Result<SlotRef<'_, T, R, N>, Full> {
+        if self.closed.load(Acquire) {
+            return Err(Full);
+        }
+
+        let mut len = self.len.load(Acquire);
+        loop {
+            if len >= self.capacity {
+                return Err(Full);
+            }
+
+            match self
+                .len
+                .compare_exchange_weak(len, len + 1, AcqRel, Acquire)
+            {
+                Ok(_) => break,
+                Err(current) => len = current,
+            }
+        }
+
+        let mut guard = R::guard();
+        let backoff = Backoff::new();
+        loop {
+            let tail = self.tail.load(Relaxed, &mut guard).unwrap();
+            let idx: usize = tail.push_idx.fetch_add(1, AcqRel);
+
+            if idx >= N {
+                if self.tail.load_raw(Relaxed) == tail.as_marked_ptr() {
+                    self.link_new_node(tail);
+                }
+                backoff.spin();
+                continue;
+            }
+
+            let slot: *const Slot<T> = &tail.elements[idx];
+            return Ok(SlotRef {
+                _guard: R::guard(),
+                slot,
+                _marker: PhantomData,
+            });
+        }
+    }
+
+    /// Attempts to claim the next element at the head of the queue and
+    /// returns a guard granting read access to it, or [`None`] if the queue
+    /// is currently empty.
+    ///
+    /// Mirrors [`pop`](Queue::pop), but lets the caller process the element
+    /// in place via [`PopRef`]'s [`Deref`] impl instead of paying for the
+    /// move out of the slot.
+    #[inline]
+    pub fn pop_ref(&self) -> Option<PopRef<'_, T, R, N>> {
+        let mut guard = R::guard();
+        let backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Acquire, &mut guard).unwrap();
+
+            let pop_idx = head.pop_idx.load(Acquire);
+            let push_idx = head.push_idx.load(Acquire);
+
+            if pop_idx >= push_idx && head.next.load_unprotected(Acquire).is_none() {
+                return None;
+            }
+
+            let idx: usize = head.pop_idx.fetch_add(1, AcqRel);
+
+            if idx < N {
+                let slot = &head.elements[idx];
+
+                // the matching `push` may still be in the middle of its tentative write, so
+                // give it a chance to catch up before conceding the slot as unreachable
+                let mut state = wait_for_init(slot);
+
+                if state == UNINIT {
+                    match slot.state.compare_exchange(UNINIT, ABANDONED, AcqRel, Acquire) {
+                        Ok(_) => {
+                            self.note_resolved(head);
+                            continue;
                         }
+                        // lost the race to a `push` that finished in between - its value is
+                        // there to read after all
+                        Err(now) => state = now,
+                    }
+                }
+
+                debug_assert_eq!(state, INIT);
+                self.len.fetch_sub(1, Relaxed);
+
+                let node: *const Node<T, R, N> = &*head;
+                return Some(PopRef {
+                    queue: self,
+                    _guard: R::guard(),
+                    node,
+                    index: idx,
+                });
+            } else {
+                // this segment is exhausted - help unlink it and retry against its successor
+                self.try_retire(head);
+                backoff.spin();
+            }
+        }
+    }
+
+    /// Pops up to `max` elements into `out`, returning how many were moved.
+    ///
+    /// Once the head segment has been fully published (its `push_idx` has
+    /// passed `N` and a next segment is already linked), up to `max` of the
+    /// remaining `pop_idx..N` slots are claimed with a single `pop_idx` CAS
+    /// instead of one `pop_idx` `fetch_add` per element - each slot still goes through the
+    /// same [`wait_for_init`]/abandon protocol [`pop`](Queue::pop) uses, so a
+    /// slow writer just costs this batch one fewer element rather than
+    /// stalling it. A segment that is still being actively pushed into falls
+    /// back to claiming one slot at a time, exactly like repeated
+    /// [`pop`](Queue::pop) calls.
+    pub fn pop_n(&self, out: &mut Vec<T>, max: usize) -> usize {
+        let mut remaining = max;
+        while remaining > 0 {
+            let mut guard = R::guard();
+            let head = self.head.load(Acquire, &mut guard).unwrap();
+            let push_idx = head.push_idx.load(Acquire);
+
+            // the head segment is still being pushed into (or is the last, possibly
+            // partial segment) - no batching is possible, fall back to `pop`
+            if push_idx < N || head.next.load_unprotected(Acquire).is_none() {
+                match self.pop() {
+                    Some(elem) => {
+                        out.push(elem);
+                        remaining -= 1;
                     }
+                    None => break,
                 }
+                continue;
             }
 
-            // head node is already drained, try to unlink and retry, or return None,
-            if idx >= NODE_SIZE {
-                match head.next.load_unprotected(SeqCst) {
-                    Some(next) => {
-                        if let Ok(unlinked) =
-                            self.head.compare_exchange(head, next, SeqCst, Relaxed)
-                        {
-                            unsafe { unlinked.retire_unchecked() };
+            let pop_idx = head.pop_idx.load(Acquire);
+            if pop_idx >= N {
+                // this segment has already been fully drained by someone else - unlink it
+                // and retry against its successor
+                self.try_retire(head);
+                continue;
+            }
+
+            let take = cmp::min(N - pop_idx, remaining);
+            let claimed_end = pop_idx + take;
+            if head
+                .pop_idx
+                .compare_exchange(pop_idx, claimed_end, AcqRel, Relaxed)
+                .is_err()
+            {
+                // lost a race for this range to a concurrent `pop`/`pop_n` - retry
+                continue;
+            }
+
+            let before = out.len();
+            for slot in &head.elements[pop_idx..claimed_end] {
+                let mut state = wait_for_init(slot);
+                if state == UNINIT {
+                    match slot.state.compare_exchange(UNINIT, ABANDONED, AcqRel, Acquire) {
+                        Ok(_) => {
+                            self.note_resolved(head);
+                            continue;
                         }
+                        Err(now) => state = now,
                     }
-                    None => return None,
-                };
-            } else {
-                let slot = &head.elements[idx];
-                if !slot.init.swap(false, Acquire) {
-                    continue;
                 }
 
-                return Some(unsafe { slot.read() });
+                debug_assert_eq!(state, INIT);
+                out.push(unsafe { slot.read() });
+                slot.state.store(CONSUMED, Release);
+                self.note_resolved(head);
+            }
+            let moved = out.len() - before;
+            self.len.fetch_sub(moved, Relaxed);
+            remaining -= moved;
+        }
+
+        max - remaining
+    }
+
+    /// Returns an iterator that pops elements from the queue in batches,
+    /// amortizing the per-segment bookkeeping [`pop_n`](Queue::pop_n) avoids
+    /// over however many elements are actually drained.
+    #[inline]
+    pub fn drain(&self) -> Drain<'_, T, R, N> {
+        Drain {
+            queue: self,
+            buf: VecDeque::new(),
+        }
+    }
+
+    /// Links a fresh, empty [`Node`] after `tail`, or helps swing `tail`
+    /// forward if another thread has already done so.
+    ///
+    /// Used by [`push_ref`](Queue::push_ref), which - unlike
+    /// [`insert_new_node`](Queue::insert_new_node) - has no element on hand
+    /// yet to eagerly write into the new node's first slot, and so simply
+    /// relies on the regular per-slot protocol for it instead.
+    #[inline]
+    fn link_new_node(&self, tail: Shared<'_, Node<T, R, N>, R>) {
+        match tail.next.load_unprotected(Acquire) {
+            None => {
+                let node: Owned<Node<T, R, N>, R> = Owned::new(Node::new());
+                // if the CAS fails, the `Owned` carried back in the `Err` is dropped here,
+                // freeing the unused node
+                let _ = tail
+                    .next
+                    .compare_exchange(Shared::none(), node, Release, Relaxed);
+            }
+            Some(next) => {
+                let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
+            }
+        }
+    }
+
+    /// Splits the queue into a [`Producer`] and a single [`Consumer`] handle
+    /// that can be sent to separate threads.
+    ///
+    /// The `Consumer` caches its current segment and read position locally
+    /// instead of re-deriving them from an atomically-loaded `head` on every
+    /// [`pop`](Consumer::pop), only reloading the shared `head` once it runs
+    /// off the end of its cached segment - a measurable win over [`pop`](Queue::pop)
+    /// over a long-running consumer. This is only sound with a single
+    /// `Consumer` reading at a time; the returned `Producer` may still be
+    /// cloned and pushed to from as many threads as needed.
+    #[inline]
+    pub fn split(self) -> (Producer<T, R, N>, Consumer<T, R, N>) {
+        let queue = Arc::new(self);
+
+        let mut guard = R::guard();
+        let (node, index) = {
+            let head = queue.head.load(Acquire, &mut guard).unwrap();
+            (&*head as *const Node<T, R, N>, head.pop_idx.load(Acquire))
+        };
+
+        (
+            Producer {
+                queue: Arc::clone(&queue),
+            },
+            Consumer {
+                queue,
+                guard,
+                node,
+                index,
+            },
+        )
+    }
+
+    /// Records that one more slot of `head` has reached a terminal state
+    /// (`CONSUMED` or `ABANDONED`) and, once every slot has, retires the
+    /// node.
+    #[inline]
+    fn note_resolved(&self, head: Shared<'_, Node<T, R, N>, R>) {
+        if head.resolved_count.fetch_add(1, AcqRel) + 1 == N {
+            self.try_retire(head);
+        }
+    }
+
+    /// Unlinks `head` in favor of its successor and hands it to the
+    /// reclamation scheme, if it has one.
+    ///
+    /// No-op if `head` has already been unlinked by a concurrent caller, or
+    /// if it has no successor yet.
+    #[inline]
+    fn try_retire(&self, head: Shared<'_, Node<T, R, N>, R>) {
+        if let Some(next) = head.next.load_unprotected(Acquire) {
+            if let Ok(unlinked) = self.head.compare_exchange(head, next, Release, Relaxed) {
+                unsafe { unlinked.retire_unchecked() };
             }
         }
     }
 
-    unsafe fn insert_new_node(&self, tail: *mut Node<T>, elem: T) -> Result<(), T> {
-        match (*tail).next.load(Acquire) {
-            ptr if ptr.is_null() => {
-                let node = Box::leak(box Node::with_tentative(&elem));
-                if (*tail)
+    fn insert_new_node(&self, tail: Shared<'_, Node<T, R, N>, R>, elem: T) -> Result<(), T> {
+        match tail.next.load_unprotected(Acquire) {
+            None => {
+                let node: Owned<Node<T, R, N>, R> = unsafe { Owned::new(Node::with_tentative(&elem)) };
+                match tail
                     .next
-                    .compare_exchange(ptr::null_mut(), node, SeqCst, Relaxed)
-                    .is_ok()
+                    .compare_exchange(Shared::none(), node, Release, Relaxed)
                 {
-                    mem::forget(elem);
-                    Ok(())
-                } else {
-                    Box::from_raw(node).reset_tentative_and_drop();
-                    Err(elem)
+                    Ok(_) => {
+                        mem::forget(elem);
+                        Ok(())
+                    }
+                    Err(fail) => {
+                        // if the insert fails, the tentative write is reversed and the node is
+                        // de-allocated again
+                        Owned::into_inner(fail.input).reset_tentative_and_drop();
+                        Err(elem)
+                    }
                 }
             }
-            next => {
-                self.tail.compare_and_swap(tail, next, Release);
+            Some(next) => {
+                let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
                 Err(elem)
             }
         }
     }
 }
 
-impl<T> Drop for Queue<T> {
+impl<T, R: GlobalReclaim, const N: usize> Drop for Queue<T, R, N> {
     #[inline]
     fn drop(&mut self) {
-        unimplemented!()
-
-        /*let mut curr = self.head.take();
+        let mut curr = self.head.take();
         while let Some(mut node) = curr {
             curr = node.next.take();
             mem::drop(node);
-        }*/
+        }
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-// Node
+// Producer
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-const NODE_SIZE: usize = 1024;
+/// The push half of a [`Queue::split`] pair.
+///
+/// Clone it to push from multiple threads; it is a thin, shared handle, not
+/// an exclusive one.
+pub struct Producer<T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    queue: Arc<Queue<T, R, N>>,
+}
 
-struct Node<T> {
+impl<T, R: GlobalReclaim, const N: usize> Producer<T, R, N> {
+    /// Pushes `elem` onto the queue; see [`Queue::push`].
+    #[inline]
+    pub fn push(&self, elem: T) -> Result<(), T> {
+        self.queue.push(elem)
+    }
+
+    /// Closes the queue; see [`Queue::close`].
+    #[inline]
+    pub fn close(&self) -> bool {
+        self.queue.close()
+    }
+
+    /// Returns `true` if the queue has been [`close`](Producer::close)d; see
+    /// [`Queue::is_closed`].
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+}
+
+impl<T, R: GlobalReclaim, const N: usize> Clone for Producer<T, R, N> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Consumer
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// The pop half of a [`Queue::split`] pair.
+///
+/// Unlike [`Producer`], this is not `Clone`: only one `Consumer` may exist
+/// per queue, since [`pop`](Consumer::pop) forgoes synchronization that only
+/// a single reader can safely skip.
+pub struct Consumer<T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    queue: Arc<Queue<T, R, N>>,
+    guard: R::Guard,
+    // invariant: always derived from a load made through `guard`, which stays pinned for
+    // the lifetime of the `Consumer` - `advance` is the only place either is replaced, and
+    // it replaces them together
+    node: *const Node<T, R, N>,
+    index: usize,
+}
+
+// SAFETY: `node` is only ever dereferenced by the thread that owns the `Consumer`, and the
+// segment it points to is kept alive by `guard`, which travels with it
+unsafe impl<T: Send, R: GlobalReclaim, const N: usize> Send for Consumer<T, R, N> {}
+
+impl<T, R: GlobalReclaim, const N: usize> Consumer<T, R, N> {
+    /// Closes the queue; see [`Queue::close`].
+    #[inline]
+    pub fn close(&self) -> bool {
+        self.queue.close()
+    }
+
+    /// Returns `true` if the queue has been [`close`](Consumer::close)d; see
+    /// [`Queue::is_closed`].
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+
+    /// Pops the next element, if any; see [`Queue::pop`].
+    ///
+    /// This claims its slot by advancing a private `index` rather than an
+    /// atomic `fetch_add`, and only reloads `self.queue`'s `head` once it
+    /// runs past the end of its cached segment - both of which are only
+    /// sound because a `Consumer` is its queue's sole reader. The queue-wide
+    /// `len` is still decremented on every successful pop.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        loop {
+            if self.index >= N {
+                // SAFETY: see the invariant documented on `Consumer::node`
+                let node = unsafe { &*self.node };
+                if node.next.load_unprotected(Acquire).is_none() {
+                    return None;
+                }
+                self.advance();
+                continue;
+            }
+
+            // SAFETY: see the invariant documented on `Consumer::node`
+            let node = unsafe { &*self.node };
+
+            let push_idx = node.push_idx.load(Acquire);
+            if self.index >= push_idx && node.next.load_unprotected(Acquire).is_none() {
+                return None;
+            }
+
+            let slot = &node.elements[self.index];
+
+            // the matching `push` may still be in the middle of its tentative write, so
+            // give it a chance to catch up before conceding the slot as unreachable
+            let mut state = wait_for_init(slot);
+
+            if state == UNINIT {
+                match slot.state.compare_exchange(UNINIT, ABANDONED, AcqRel, Acquire) {
+                    Ok(_) => {
+                        self.index += 1;
+                        continue;
+                    }
+                    Err(now) => state = now,
+                }
+            }
+
+            debug_assert_eq!(state, INIT);
+            let elem = unsafe { slot.read() };
+            slot.state.store(CONSUMED, Release);
+            self.index += 1;
+            self.queue.len.fetch_sub(1, Relaxed);
+
+            return Some(elem);
+        }
+    }
+
+    /// Retires the now fully-drained current segment and picks up its
+    /// successor, the one point where [`pop`](Consumer::pop) pays for
+    /// synchronizing against shared state.
+    fn advance(&mut self) {
+        // SAFETY: see the invariant documented on `Consumer::node`
+        let node = unsafe { &*self.node };
+
+        // `pop` only calls this once it has seen the segment's successor linked, so this
+        // finds it on the first try
+        let backoff = Backoff::new();
+        while node.next.load_unprotected(Acquire).is_none() {
+            backoff.snooze();
+        }
+
+        // reload `head` to obtain a `Shared` for `try_retire`'s `compare_exchange` - since a
+        // `Consumer` is its queue's sole reader, this can only still be `self.node` itself
+        let head = self.queue.head.load(Acquire, &mut self.guard).unwrap();
+        self.queue.try_retire(head);
+
+        // the successor is now `head`; load it through `guard` as the invariant requires
+        let next = self.queue.head.load(Acquire, &mut self.guard).unwrap();
+        self.node = &*next as *const Node<T, R, N>;
+        self.index = 0;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Node
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct Node<T, R, const N: usize> {
     push_idx: AtomicUsize,    // CacheAligned
     pop_idx: AtomicUsize,     // CacheAligned
-    next: AtomicPtr<Node<T>>, // CacheAligned
-    elements: [Slot<T>; NODE_SIZE],
+    next: Atomic<Node<T, R, N>, R>, // CacheAligned
+    /// Number of slots that have reached a terminal state (`CONSUMED` or
+    /// `ABANDONED`); once this reaches `N`, the node is safe to unlink.
+    resolved_count: AtomicUsize,
+    elements: [Slot<T>; N],
 }
 
-impl<T> Node<T> {
+impl<T, R, const N: usize> Node<T, R, N> {
     #[inline]
     fn new() -> Self {
         Self {
             push_idx: AtomicUsize::new(0),
             pop_idx: AtomicUsize::new(0),
-            next: AtomicPtr::new(ptr::null_mut()),
+            next: Atomic::null(),
+            resolved_count: AtomicUsize::new(0),
             elements: unsafe { Self::init_elements_arr() },
         }
     }
@@ -216,16 +857,19 @@ impl<T> Node<T> {
     unsafe fn with_tentative(elem: &T) -> Self {
         let elements = Self::init_elements_arr();
 
+        // no `pop` can observe index 0 before this node is linked into the list, so there is
+        // no concurrent abandoner to race against here
         let first = &elements[0];
-        (&mut *first.inner.get())
-            .as_mut_ptr()
-            .copy_from_nonoverlapping(elem, 1);
-        first.init.store(true, Relaxed);
+        first
+            .inner
+            .with_mut(|cell| (&mut *cell).as_mut_ptr().copy_from_nonoverlapping(elem, 1));
+        first.state.store(INIT, Relaxed);
 
         Self {
             push_idx: AtomicUsize::new(1),
             pop_idx: AtomicUsize::new(0),
-            next: AtomicPtr::new(ptr::null_mut()),
+            next: Atomic::null(),
+            resolved_count: AtomicUsize::new(0),
             elements,
         }
     }
@@ -236,11 +880,11 @@ impl<T> Node<T> {
     }
 
     #[inline]
-    unsafe fn init_elements_arr() -> [Slot<T>; NODE_SIZE] {
-        let mut uninit: MaybeUninit<[Slot<T>; NODE_SIZE]> = MaybeUninit::uninit();
+    unsafe fn init_elements_arr() -> [Slot<T>; N] {
+        let mut uninit: MaybeUninit<[Slot<T>; N]> = MaybeUninit::uninit();
         let first = uninit.as_mut_ptr() as *mut Slot<T>;
 
-        for i in 0..NODE_SIZE {
+        for i in 0..N {
             first.add(i).write(Slot::new());
         }
 
@@ -248,18 +892,19 @@ impl<T> Node<T> {
     }
 }
 
-impl<T> Drop for Node<T> {
+impl<T, R, const N: usize> Drop for Node<T, R, N> {
     #[inline]
     fn drop(&mut self) {
         let start: usize = self.pop_idx.load(Relaxed);
-        let end: usize = cmp::min(self.push_idx.load(Relaxed), NODE_SIZE);
+        let end: usize = cmp::min(self.push_idx.load(Relaxed), N);
 
         // TODO: what if panic?
         for slot in &mut self.elements[start..end] {
-            debug_assert!(slot.init.load(Relaxed));
-            unsafe {
-                let inner = &mut *slot.inner.get();
-                ptr::drop_in_place(inner.as_mut_ptr());
+            // only an `INIT` slot holds a live, not-yet-read-out value; `CONSUMED` and
+            // `ABANDONED` slots have nothing left to drop, and `UNINIT` never held one
+            if slot.state.load(Relaxed) == INIT {
+                slot.inner
+                    .with_mut(|cell| unsafe { ptr::drop_in_place((&mut *cell).as_mut_ptr()) });
             }
         }
     }
@@ -269,10 +914,20 @@ impl<T> Drop for Node<T> {
 // Slot
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// A slot has not yet been written to by its matching `push`.
 const UNINIT: usize = 0;
+/// A `push` has written its element and the slot is ready to be read.
+const INIT: usize = 1;
+/// A `pop` gave up waiting for the matching `push` and abandoned the slot;
+/// the `push` must notice this and relocate its element elsewhere.
+const ABANDONED: usize = 2;
+/// A `pop` has read the element back out; terminal, like `ABANDONED`.
+const CONSUMED: usize = 3;
 
 struct Slot<T> {
     inner: UnsafeCell<MaybeUninit<T>>,
+    /// One of `UNINIT`, `INIT`, `ABANDONED` or `CONSUMED`; see the constants
+    /// above for the transitions between these states.
     state: AtomicUsize,
 }
 
@@ -289,11 +944,255 @@ impl<T> Slot<T> {
 
     #[inline]
     unsafe fn read(&self) -> T {
-        (&*self.inner.get()).as_ptr().read()
+        self.inner.with(|cell| (&*cell).as_ptr().read())
     }
 
     #[inline]
     unsafe fn write(&self, elem: T) {
-        (&mut *self.inner.get()).as_mut_ptr().write(elem)
+        self.inner.with_mut(|cell| (&mut *cell).as_mut_ptr().write(elem))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Backoff
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+const SPIN_LIMIT: u32 = 6;
+
+/// A `core`-only exponential backoff helper, analogous to
+/// [`crate::backoff::Backoff`] but usable from this `#![no_std]`-clean
+/// module (see the note on [`crate::facade`]): retrying a
+/// failed CAS or waiting for a concurrently written value in a tight loop
+/// wastes CPU cycles and creates needless contention on the cache line being
+/// polled, so this escalates from a handful of spin iterations to yielding
+/// the thread (where [`facade::thread`](crate::facade::thread) is available)
+/// once a threshold is crossed.
+struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    #[inline]
+    fn new() -> Self {
+        Self { step: Cell::new(0) }
+    }
+
+    /// Backs off in a lock-free CAS retry loop.
+    ///
+    /// Only ever busy-spins; never yields the thread, since a CAS retry loop
+    /// must not block.
+    #[inline]
+    fn spin(&self) {
+        for _ in 0..1u32 << self.step.get().min(SPIN_LIMIT) {
+            core::hint::spin_loop();
+        }
+
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Backs off while waiting for a condition that another thread must
+    /// bring about (e.g. a concurrently written value becoming visible).
+    ///
+    /// Escalates to yielding the thread once [`is_completed`](Backoff::is_completed).
+    #[inline]
+    fn snooze(&self) {
+        if self.is_completed() {
+            Self::yield_now();
+        } else {
+            for _ in 0..1u32 << self.step.get() {
+                core::hint::spin_loop();
+            }
+
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Returns `true` once the backoff has escalated past pure spinning and
+    /// should yield the thread instead.
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.step.get() > SPIN_LIMIT
+    }
+
+    #[cfg(any(loom, not(feature = "portable-atomic")))]
+    #[inline]
+    fn yield_now() {
+        crate::facade::thread::yield_now();
+    }
+
+    // bare-metal/embedded targets generally have no thread scheduler to yield to
+    #[cfg(all(not(loom), feature = "portable-atomic"))]
+    #[inline]
+    fn yield_now() {
+        core::hint::spin_loop();
+    }
+}
+
+/// Spins with exponential backoff until `slot` leaves `UNINIT`, or the
+/// backoff's spin budget is exhausted - whichever comes first; returns the
+/// last observed state either way.
+///
+/// This does not block indefinitely: if the matching `push` is simply slow,
+/// the caller proceeds to claim the slot as `ABANDONED` anyway, and the
+/// `push` notices via its own `compare_exchange` and relocates.
+#[inline]
+fn wait_for_init<T>(slot: &Slot<T>) -> usize {
+    let backoff = Backoff::new();
+    loop {
+        let state = slot.state.load(Acquire);
+        if state != UNINIT || backoff.is_completed() {
+            return state;
+        }
+        backoff.spin();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Full
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Error returned by [`push_ref`](Queue::push_ref) when the queue is
+/// [`bounded`](Queue::bounded) and already holds [`capacity`](Queue::capacity)
+/// elements, or has been [`close`](Queue::close)d.
+#[derive(Debug)]
+pub struct Full;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// SlotRef
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A guard granting direct, uninitialized write access to a slot reserved by
+/// [`push_ref`](Queue::push_ref).
+///
+/// Dropping the guard publishes the (hopefully now initialized) slot to
+/// consumers, exactly as a successful [`push`](Queue::push) call would.
+pub struct SlotRef<'q, T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    _guard: R::Guard,
+    slot: *const Slot<T>,
+    _marker: PhantomData<&'q Queue<T, R, N>>,
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> SlotRef<'q, T, R, N> {
+    #[inline]
+    fn slot(&self) -> &Slot<T> {
+        unsafe { &*self.slot }
+    }
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Deref for SlotRef<'q, T, R, N> {
+    type Target = MaybeUninit<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.slot().inner.with(|cell| unsafe { &*cell })
+    }
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> DerefMut for SlotRef<'q, T, R, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.slot().inner.with_mut(|cell| unsafe { &mut *cell })
+    }
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Drop for SlotRef<'q, T, R, N> {
+    #[inline]
+    fn drop(&mut self) {
+        match self.slot().state.compare_exchange(UNINIT, INIT, AcqRel, Acquire) {
+            Ok(_) => {}
+            Err(_) => {
+                // a `pop`/`pop_ref` already gave up on this slot (ABANDONED) before we could
+                // publish it; whatever was written here will never be read by anyone, so
+                // dispose of it here instead of leaking it
+                self.slot()
+                    .inner
+                    .with_mut(|cell| unsafe { ptr::drop_in_place((&mut *cell).as_mut_ptr()) });
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// PopRef
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A guard granting read access to an element claimed by
+/// [`pop_ref`](Queue::pop_ref), without moving it out of its slot.
+pub struct PopRef<'q, T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    queue: &'q Queue<T, R, N>,
+    _guard: R::Guard,
+    node: *const Node<T, R, N>,
+    index: usize,
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> PopRef<'q, T, R, N> {
+    #[inline]
+    fn slot(&self) -> &Slot<T> {
+        unsafe { &(*self.node).elements[self.index] }
+    }
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Deref for PopRef<'q, T, R, N> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.slot().inner.with(|cell| unsafe { &*(&*cell).as_ptr() })
+    }
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Drop for PopRef<'q, T, R, N> {
+    #[inline]
+    fn drop(&mut self) {
+        // `pop_ref` never moves the element out of its slot - this is the only place it is
+        // actually disposed of
+        self.slot()
+            .inner
+            .with_mut(|cell| unsafe { ptr::drop_in_place((&mut *cell).as_mut_ptr()) });
+        self.slot().state.store(CONSUMED, Release);
+
+        // best-effort early retirement, mirroring `note_resolved`'s usual call site in `pop`:
+        // if `node` is no longer `head` by the time this runs, a later `pop`/`pop_ref` call on
+        // some other segment boundary retires it instead
+        let mut guard = R::guard();
+        if let Some(head) = self.queue.head.load(Acquire, &mut guard) {
+            if ptr::eq(&*head as *const Node<T, R, N>, self.node) {
+                self.queue.note_resolved(head);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Drain
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// An iterator that drains a [`Queue`] via [`pop_n`](Queue::pop_n), returned
+/// by [`Queue::drain`].
+pub struct Drain<'q, T, R: GlobalReclaim, const N: usize = DEFAULT_NODE_SIZE> {
+    queue: &'q Queue<T, R, N>,
+    buf: VecDeque<T>,
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Drain<'q, T, R, N> {
+    /// Number of elements fetched from the queue per refill of `buf`.
+    const BATCH: usize = 32;
+}
+
+impl<'q, T, R: GlobalReclaim, const N: usize> Iterator for Drain<'q, T, R, N> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if let Some(elem) = self.buf.pop_front() {
+            return Some(elem);
+        }
+
+        let mut batch = Vec::with_capacity(Self::BATCH);
+        self.queue.pop_n(&mut batch, Self::BATCH);
+        self.buf.extend(batch);
+        self.buf.pop_front()
     }
 }
@@ -0,0 +1,56 @@
+//! Indirection layer over the atomic and cell primitives used by
+//! [`crate::faa2`], so that the same source can run either against `std`/`core`
+//! or, under `--cfg loom`, against `loom`'s instrumented equivalents for
+//! exhaustive concurrency model checking.
+//!
+//! The `portable-atomic` feature additionally swaps the plain `core::sync`
+//! atomics for the `portable-atomic` crate's polyfilled ones, for targets
+//! (e.g. `thumbv7m-none-eabi`) whose hardware lacks native CAS.
+//!
+//! Note: only `faa2` and this module are `#![no_std]`-clean today; `faa` and
+//! the crate root still use `std` paths unconditionally. `faa2` depends on
+//! `reclaim` just as `faa` and the crate root do.
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::thread;
+
+#[cfg(loom)]
+pub(crate) use loom::cell::UnsafeCell;
+
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+
+// threads are not generally available on the bare-metal/embedded targets
+// `portable-atomic` is meant for, so this is only re-exported for ordinary
+// `std` builds; consumed by `faa2`'s local `Backoff::yield_now`
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use std::thread;
+
+/// A `core::cell::UnsafeCell` exposing the same `with`/`with_mut` closure API
+/// as `loom::cell::UnsafeCell`, so callers don't need a second code path for
+/// the non-loom build.
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    #[inline]
+    pub(crate) fn new(data: T) -> Self {
+        Self(core::cell::UnsafeCell::new(data))
+    }
+
+    #[inline]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    #[inline]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
@@ -1,5 +1,12 @@
+// `faa2` and `facade` only depend on `alloc` (for `Box`, `Arc`, `Vec` and
+// `VecDeque`) plus, by default, `std::thread` - see `facade`'s module docs for
+// the current no_std boundary.
+extern crate alloc;
+
+mod backoff;
 mod faa;
-mod faa2;
+pub mod faa2;
+mod facade;
 
 use std::mem::MaybeUninit;
 use std::ptr;
@@ -0,0 +1,48 @@
+//! Basic correctness tests for `faa::Queue`'s public API,
+//! re-exported at the crate root as [`FAAQueue`](conquer_queue::FAAQueue).
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use conquer_queue::FAAQueue;
+
+type Queue<T> = FAAQueue<T, reclaim::leak::Leak>;
+
+#[test]
+fn push_pop_preserves_order() {
+    let queue = Queue::new();
+    queue.push(1);
+    queue.push(2);
+    queue.push(3);
+
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(3));
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn pop_wait_returns_an_already_pushed_element() {
+    let queue = Queue::new();
+    queue.push(7);
+
+    assert_eq!(queue.pop_wait(), 7);
+}
+
+#[test]
+fn pop_wait_is_woken_by_a_later_push_from_another_thread() {
+    let queue = Arc::new(Queue::new());
+
+    let consumer = {
+        let queue = Arc::clone(&queue);
+        thread::spawn(move || queue.pop_wait())
+    };
+
+    // give the consumer a head start so it actually blocks on an empty queue
+    // before the element arrives, rather than racing it
+    thread::sleep(Duration::from_millis(50));
+    queue.push(42);
+
+    assert_eq!(consumer.join().unwrap(), 42);
+}
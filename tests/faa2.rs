@@ -0,0 +1,107 @@
+//! Basic single-threaded correctness tests for `faa2::Queue`'s public API.
+
+use conquer_queue::faa2::Queue;
+
+type Q<T> = Queue<T, reclaim::leak::Leak>;
+
+#[test]
+fn push_pop_preserves_order() {
+    let queue = Q::new();
+    queue.push(1).unwrap();
+    queue.push(2).unwrap();
+    queue.push(3).unwrap();
+
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(3));
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn bounded_push_rejects_once_full() {
+    let queue: Q<usize> = Queue::bounded(2);
+
+    assert!(queue.push(1).is_ok());
+    assert!(queue.push(2).is_ok());
+    assert_eq!(queue.push(3), Err(3));
+    assert!(queue.is_full());
+
+    assert_eq!(queue.pop(), Some(1));
+    assert!(queue.push(3).is_ok());
+}
+
+#[test]
+fn try_push_respects_bound() {
+    let queue: Q<usize> = Queue::bounded(2);
+
+    assert!(queue.try_push(1).is_ok());
+    assert!(queue.try_push(2).is_ok());
+    assert_eq!(queue.try_push(3), Err(3));
+
+    assert_eq!(queue.pop(), Some(1));
+    assert!(queue.try_push(3).is_ok());
+}
+
+#[test]
+fn close_rejects_pushes_but_existing_elements_still_drain() {
+    let queue = Q::new();
+    queue.push(1).unwrap();
+    queue.push(2).unwrap();
+
+    assert!(queue.close());
+    assert!(!queue.close()); // already closed, second call reports that
+    assert!(queue.is_closed());
+    assert_eq!(queue.push(3), Err(3));
+
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn push_ref_publishes_on_drop() {
+    let queue = Q::new();
+    {
+        let mut slot = queue.push_ref().unwrap();
+        slot.write(42);
+    }
+
+    assert_eq!(queue.pop(), Some(42));
+}
+
+#[test]
+fn pop_ref_reads_without_moving_and_drops_in_place() {
+    let queue = Q::new();
+    queue.push(String::from("hello")).unwrap();
+
+    let popped = queue.pop_ref().unwrap();
+    assert_eq!(&*popped, "hello");
+    drop(popped);
+
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn split_producer_consumer_roundtrip() {
+    let queue = Q::new();
+    let (producer, mut consumer) = queue.split();
+
+    for i in 0..5 {
+        producer.push(i).unwrap();
+    }
+
+    let popped: Vec<_> = std::iter::from_fn(|| consumer.pop()).collect();
+    assert_eq!(popped, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn split_close_forwards_to_the_shared_queue() {
+    let queue = Q::new();
+    let (producer, consumer) = queue.split();
+
+    assert!(!producer.is_closed());
+    assert!(consumer.close());
+
+    assert!(producer.is_closed());
+    assert_eq!(producer.push(1), Err(1));
+}
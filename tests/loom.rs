@@ -0,0 +1,55 @@
+//! Model-checks `faa2::Queue` push/pop interleavings under `loom`.
+//!
+//! Run with `RUSTFLAGS="--cfg loom" LOOM_MAX_PREEMPTIONS=2 cargo test --test loom --release`.
+//!
+//! Note: `faa2::Queue` now reclaims nodes through the `reclaim` crate's
+//! `GlobalReclaim` (see its module docs), whose own internals are not
+//! loom-instrumented - this only exercises the `push`/`pop` index bookkeeping
+//! this module owns directly, not the reclamation scheme itself.
+
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+
+use conquer_queue::faa2::Queue;
+
+/// Pushes and pops a handful of elements across a couple of producer and
+/// consumer threads, and asserts that every pushed element is popped exactly
+/// once, including across a node rollover (`push_idx` crossing the segment
+/// size `N`, here 2).
+#[test]
+fn no_lost_or_duplicated_elements_across_rollover() {
+    loom::model(|| {
+        let queue = Arc::new(Queue::<usize, reclaim::leak::Leak, 2>::new());
+
+        let producers: Vec<_> = (0..2)
+            .map(|p| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    for i in 0..2 {
+                        queue.push(p * 2 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        let mut popped = Vec::new();
+        for _ in 0..4 {
+            if let Some(elem) = queue.pop() {
+                popped.push(elem);
+            }
+        }
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        while let Some(elem) = queue.pop() {
+            popped.push(elem);
+        }
+
+        popped.sort_unstable();
+        assert_eq!(popped, vec![0, 1, 2, 3]);
+    });
+}
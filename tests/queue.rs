@@ -0,0 +1,16 @@
+//! Basic single-threaded correctness tests for the Michael-Scott [`Queue`].
+
+use conquer_queue::Queue;
+
+type Q<T> = Queue<T, reclaim::leak::Leak>;
+
+#[test]
+fn push_pop_preserves_order() {
+    let queue = Q::new();
+    queue.push(1);
+    queue.push(2);
+
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), None);
+}